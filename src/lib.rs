@@ -1,16 +1,20 @@
 //! # 最短経路探索
-//! 
+//!
 //! `shortestpath`は、最短経路探索を行うためのライブラリです。
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::collections::HashMap;
 
 pub type GraphIndex = String;
+pub type EdgeName = String;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Node {
     name: GraphIndex,
     done: bool,
     edges: Vec<Edge>,
     costed: i32,
+    visited: bool,
     prev: Option<GraphIndex>,
     passage: Option<Edge>,
 }
@@ -18,11 +22,11 @@ pub struct Node {
 #[derive(Debug, Clone)]
 pub struct Edge {
     next: GraphIndex,
-    name: String,
+    name: EdgeName,
     cost: i32,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Graph {
     nodes: HashMap<GraphIndex, Node>
 }
@@ -48,6 +52,7 @@ impl Graph {
             done: false,
             edges: vec![],
             costed: -1,
+            visited: false,
             prev: Some(src.to_string()),
             passage: None,
         });
@@ -57,6 +62,7 @@ impl Graph {
             done: false,
             edges: vec![],
             costed: -1,
+            visited: false,
             prev: None,
             passage: None,
         });
@@ -81,6 +87,18 @@ impl Graph {
         }
     }
 
+    /// `name`のノードに到達コストが一度でも記録されたかどうかを返す。
+    ///
+    /// `costed`の値そのもの（`-1`が「未到達」を表す従来の慣習）とは切り離して扱うための
+    /// フラグで、コストに負の辺重みが混ざっていても「未到達」を誤検知しない。
+    fn node_visited<'a>(&self, name: &'a str) -> bool {
+        if let Some(node) = self.nodes.get(name) {
+            node.visited
+        } else {
+            false
+        }
+    }
+
     fn node_edges<'a>(&self, name: &'a str) -> Vec<Edge> {
         if let Some(node) = self.nodes.get(name) {
             node.edges.clone()
@@ -100,6 +118,7 @@ impl Graph {
     fn update_node_edge<'a>(&mut self, next_node_name: &'a str, cost: i32, done_node_name: &'a str, passed_edge: Edge) {
         if let Some(node) = self.nodes.get_mut(next_node_name) {
             node.costed = cost;
+            node.visited = true;
             node.prev = Some(done_node_name.to_string());
             node.passage = Some(passed_edge);
         }
@@ -130,79 +149,738 @@ impl Graph {
     /// let result = g.shortest_path("s", "z");
     /// ```
     pub fn shortest_path<'a>(&mut self, start: &'a str, goal: &'a str) -> ShortestPath {
+        if !self.nodes.contains_key(start) {
+            return ShortestPath {passages: vec![], total_cost: -1};
+        }
+
+        self.dijkstra_core(start, Some(goal), |_, cost| cost);
+        self.reconstruct_path(start, goal)
+    }
+
+    /// `start`から、二分ヒープによる優先度付きキューを使ってダイクストラ法で探索する。
+    ///
+    /// ヒープには`(優先度, 実コスト, ノード名)`を積み、ポップ時に`self.nodes`へ記録済みの
+    /// コストより古いエントリ（stale）であれば無視する。`priority`はヒープの並び順だけを
+    /// 決める関数で、最短経路本体には素のコストを、A*探索にはコストとヒューリスティックの
+    /// 合計を渡す。`goal`に`Some`を渡すとそのノードが確定した時点で打ち切り、`None`なら
+    /// 到達可能な全ノードが確定するまで探索を続ける（全始点ルーティング表の構築に使う）。
+    fn dijkstra_core<'a, F>(&mut self, start: &'a str, goal: Option<&'a str>, mut priority: F)
+    where
+        F: FnMut(&str, i32) -> i32,
+    {
         if let Some(start_node) = self.nodes.get_mut(start) {
             start_node.costed = 0;
+            start_node.visited = true;
+        }
 
-            loop {
-                let mut done_node : Option<String> = None;
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse((priority(start, 0), 0, start.to_string())));
 
-                for (name, node) in &self.nodes {
-                    if node.done || node.costed < 0 {
-                        continue;
-                    }
+        while let Some(Reverse((_, cost, node_name))) = heap.pop() {
+            if self.is_done_node(&node_name) {
+                continue;
+            }
 
-                    if done_node.is_none() {
-                        done_node = Some(name.to_string());
-                    } else if node.costed < self.node_costed(done_node.clone().unwrap().as_ref()) {
-                        done_node = Some(name.to_string());
-                    }
+            if cost > self.node_costed(&node_name) {
+                continue;
+            }
+
+            for edge in self.node_edges(&node_name) {
+                let passed_edge = edge.clone();
+                let next_node = edge.next;
+
+                if self.is_done_node(next_node.as_ref()) {
+                    continue;
                 }
 
-                if done_node.is_none() {
+                let new_cost = cost + edge.cost;
+                let next_node_costed = self.node_costed(&next_node);
+                if !self.node_visited(&next_node) || new_cost < next_node_costed {
+                    self.update_node_edge(&next_node, new_cost, &node_name, passed_edge);
+                    heap.push(Reverse((priority(&next_node, new_cost), new_cost, next_node)));
+                }
+            }
+
+            self.update_node_done(&node_name, true);
+
+            if Some(node_name.as_str()) == goal {
+                break;
+            }
+        }
+    }
+
+    /// `goal`から`prev`/`passage`を辿って`start`まで遡り、`ShortestPath`を組み立てる。
+    fn reconstruct_path<'a>(&self, start: &'a str, goal: &'a str) -> ShortestPath {
+        if goal != start && !self.node_visited(goal) {
+            return ShortestPath {passages: vec![], total_cost: -1};
+        }
+
+        let mut passages = vec![];
+        let mut node_name = goal.to_string();
+        loop {
+            if let Some(node) = self.nodes.get(&node_name) {
+                passages.push(WayKind::Node(node.name.clone()));
+
+                if let Some(ref passed_edge) = node.passage {
+                    passages.push(WayKind::Edge(passed_edge.name.clone(), passed_edge.cost));
+                }
+
+                if node.name == start {
                     break;
                 }
+                node_name = self.node_prev(&node_name).unwrap();
+            } else {
+                break;
+            }
+        }
+
+        passages.reverse();
+        ShortestPath {passages: passages, total_cost: self.nodes.get(goal).unwrap().costed}
+    }
+
+    /// `start`から`goal`までの最短経路を、ヒューリスティック関数`estimate`を使ったA*探索で求める。
+    ///
+    /// `estimate(node)`は、`node`から`goal`までの残りコストを決して過大評価しない下界
+    /// （admissible heuristic）であるだけでなく、辺`(u, v)`ごとに`estimate(u) <= edge.cost +
+    /// estimate(v)`を満たす一貫性（consistent / monotone heuristic）も必要となる。`dijkstra_core`
+    /// は一度`done`にしたノードを再展開しないため、一貫性のないヒューリスティックを渡すと
+    /// 探索が真の最短コストより大きい値で確定してしまうことがある。探索自体は`dijkstra_core`と
+    /// 同じ緩和処理を行い、優先度だけを`costed + estimate(node)`に変えている。`Node::costed`には
+    /// 常に実コストが積まれるため、一貫性を満たす限り`ShortestPath::cost()`は通常の
+    /// `shortest_path`と同様に厳密な値を返す。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shortestpath::new_graph;
+    /// let mut g = new_graph();
+    ///
+    /// g.add("s", "a", 2, "edge1");
+    /// g.add("a", "z", 2, "edge2");
+    /// let result = g.astar("s", "z", |_| 0);
+    /// ```
+    pub fn astar<'a, F>(&mut self, start: &'a str, goal: &'a str, estimate: F) -> ShortestPath
+    where
+        F: Fn(&str) -> i32,
+    {
+        if !self.nodes.contains_key(start) {
+            return ShortestPath {passages: vec![], total_cost: -1};
+        }
+
+        self.dijkstra_core(start, Some(goal), |name, cost| cost + estimate(name));
+        self.reconstruct_path(start, goal)
+    }
+
+    /// `start`から`goal`までの最短経路を、ベルマン・フォード法で求める。
+    ///
+    /// `shortest_path`・`astar`と異なり辺に負のコストがあっても構わない。全ての辺をノード数
+    /// - 1回緩和した後、もう一度だけ緩和を試みて、まだコストが更新できるノードがあれば
+    /// 到達可能な負閉路が存在すると判断し`None`を返す。負閉路が無ければ`prev`/`passage`は
+    /// `reconstruct_path`でそのまま使えるので、経路復元はダイクストラ版と共通化している。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shortestpath::new_graph;
+    /// let mut g = new_graph();
+    ///
+    /// g.add("s", "a", 2, "edge1");
+    /// g.add("a", "z", -1, "edge2");
+    /// let result = g.shortest_path_bellman_ford("s", "z").unwrap();
+    /// ```
+    pub fn shortest_path_bellman_ford<'a>(&mut self, start: &'a str, goal: &'a str) -> Option<ShortestPath> {
+        if !self.nodes.contains_key(start) {
+            return Some(ShortestPath {passages: vec![], total_cost: -1});
+        }
+
+        if let Some(start_node) = self.nodes.get_mut(start) {
+            start_node.costed = 0;
+            start_node.visited = true;
+        }
 
-                let done_node_name = done_node.unwrap();
+        let names: Vec<String> = self.nodes.keys().cloned().collect();
 
-                for edge in self.node_edges(&done_node_name.clone()) {
-                    let passed_edge = edge.clone();
-                    let next_node = edge.next;
+        for _ in 0..names.len().saturating_sub(1) {
+            let mut updated = false;
 
-                    if self.is_done_node(next_node.as_ref()) {
-                        continue;
+            for name in &names {
+                if !self.node_visited(name) {
+                    continue;
+                }
+
+                let cost = self.node_costed(name);
+                for edge in self.node_edges(name) {
+                    let new_cost = cost + edge.cost;
+                    let next_cost = self.node_costed(&edge.next);
+                    if !self.node_visited(&edge.next) || new_cost < next_cost {
+                        let next_node = edge.next.clone();
+                        self.update_node_edge(&next_node, new_cost, name, edge);
+                        updated = true;
                     }
+                }
+            }
 
-                    let new_cost = self.node_costed(&done_node_name.clone()) + edge.cost;
-                    let next_node_costed = self.node_costed(&next_node.clone());
-                    if next_node_costed == -1 || new_cost < next_node_costed {
-                        self.update_node_edge(&next_node, new_cost, &done_node_name, passed_edge);
+            if !updated {
+                break;
+            }
+        }
+
+        for name in &names {
+            if !self.node_visited(name) {
+                continue;
+            }
+
+            let cost = self.node_costed(name);
+            for edge in self.node_edges(name) {
+                let new_cost = cost + edge.cost;
+                let next_cost = self.node_costed(&edge.next);
+                if !self.node_visited(&edge.next) || new_cost < next_cost {
+                    return None;
+                }
+            }
+        }
+
+        Some(self.reconstruct_path(start, goal))
+    }
+
+    /// `start`から`goal`までのコストが小さい順に、最大`k`本の経路をYen's algorithmで列挙する。
+    ///
+    /// まず`shortest_path`でベストな経路を求め、以降はその経路上の各ノードを「スパーノード」
+    /// として、そこまでの根経路（root path）を固定したまま、既に見つかった経路群が根経路を
+    /// 再現してしまう辺だけを取り除いたグラフ上でスパーノードから`goal`まで再探索する。
+    /// こうして得た根経路＋スパー経路の候補をコスト順のキューに積み、まだ見つかっていない
+    /// ものの中で最も安いものを毎回採用する。見つかった経路（と検討済み候補）は辺名の
+    /// 列をキーにした`PathTrie`に格納し、共有する辺の並びを重複して持たずに重複検出する。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shortestpath::new_graph;
+    /// let mut g = new_graph();
+    ///
+    /// g.add("s", "a", 2, "edge1");
+    /// g.add("s", "b", 2, "edge2");
+    /// g.add("a", "z", 2, "edge3");
+    /// g.add("b", "z", 2, "edge4");
+    /// let result = g.k_shortest_paths("s", "z", 2);
+    /// ```
+    pub fn k_shortest_paths<'a>(&mut self, start: &'a str, goal: &'a str, k: usize) -> Vec<ShortestPath> {
+        let mut found: Vec<ShortestPath> = vec![];
+
+        if k == 0 || !self.nodes.contains_key(start) {
+            return found;
+        }
+
+        let edge_lookup = self.edge_lookup();
+        let mut trie = PathTrie::new();
+
+        let first = self.shortest_path(start, goal);
+        if first.cost() < 0 {
+            return found;
+        }
+        trie.insert(&first.edge_names(), first.cost());
+        found.push(first);
+
+        let mut candidates: BinaryHeap<Reverse<(i32, Vec<EdgeName>)>> = BinaryHeap::new();
+
+        while found.len() < k {
+            let prev_nodes = found[found.len() - 1].node_names();
+            let prev_edges = found[found.len() - 1].edge_names();
+
+            for i in 0..prev_edges.len() {
+                let spur_node = prev_nodes[i].clone();
+                let root_nodes: Vec<GraphIndex> = prev_nodes[..=i].to_vec();
+                let root_edges: Vec<EdgeName> = prev_edges[..i].to_vec();
+                let root_cost: i32 = root_edges.iter().filter_map(|name| edge_lookup.get(name)).map(|edge| edge.cost).sum();
+
+                let mut working = self.clone();
+                working.reset_search_state();
+
+                for path in &found {
+                    let path_edges = path.edge_names();
+                    if path_edges.len() > i && path_edges[..i] == root_edges[..] {
+                        let removed_edge = &path_edges[i];
+                        if let Some(node) = working.nodes.get_mut(&root_nodes[i]) {
+                            node.edges.retain(|edge| &edge.name != removed_edge);
+                        }
                     }
                 }
 
-                self.update_node_done(&done_node_name, true);
-                
-                if done_node_name == goal {
-                    break;
+                for root_node_name in &root_nodes[..root_nodes.len() - 1] {
+                    working.nodes.remove(root_node_name);
+                }
+
+                let spur_path = working.shortest_path(&spur_node, goal);
+                if spur_path.cost() < 0 {
+                    continue;
+                }
+
+                let mut total_edges = root_edges.clone();
+                total_edges.extend(spur_path.edge_names());
+                let total_cost = root_cost + spur_path.cost();
+
+                if trie.contains(&total_edges) {
+                    continue;
+                }
+                trie.insert(&total_edges, total_cost);
+                candidates.push(Reverse((total_cost, total_edges)));
+            }
+
+            match candidates.pop() {
+                Some(Reverse((cost, edges))) => found.push(self.path_from_edges(start, &edges, cost, &edge_lookup)),
+                None => break,
+            }
+        }
+
+        found
+    }
+
+    /// `start`から`goal`までの最短経路を求め、同コストの経路が複数ある場合は、開始ノードから
+    /// 辿るノード名の列が辞書順で最小になるものを決定的に選ぶ。
+    ///
+    /// 比較キーは「開始ノードからの通過ノード名の列」そのもの（`Vec<String>`の標準の`Ord`、
+    /// つまり要素ごとの辞書式比較）。緩和中に`new_cost == next_node_costed`となった場合、
+    /// 候補の通過ノード列が現在記録されているものより辞書順で小さいときだけ`prev`/`passage`
+    /// を更新する。ヒープは`(コスト, 通過ノード列)`の順に取り出すので、コスト0の辺で繋がった
+    /// 同コストのノード同士でも、辞書順で小さい経路の先行ノードが先に確定する。こうすることで、
+    /// `HashMap`の走査順に依存せず、プラットフォームをまたいで同じ結果が再現される。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shortestpath::new_graph;
+    /// let mut g = new_graph();
+    ///
+    /// g.add("s", "a", 2, "edge1");
+    /// g.add("s", "b", 2, "edge2");
+    /// g.add("a", "z", 2, "edge3");
+    /// g.add("b", "z", 2, "edge4");
+    /// let result = g.shortest_path_lexicographic("s", "z");
+    /// ```
+    pub fn shortest_path_lexicographic<'a>(&mut self, start: &'a str, goal: &'a str) -> ShortestPath {
+        if !self.nodes.contains_key(start) {
+            return ShortestPath {passages: vec![], total_cost: -1};
+        }
+
+        if let Some(start_node) = self.nodes.get_mut(start) {
+            start_node.costed = 0;
+            start_node.visited = true;
+        }
+
+        let mut path_keys: HashMap<GraphIndex, Vec<GraphIndex>> = HashMap::new();
+        path_keys.insert(start.to_string(), vec![start.to_string()]);
+
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse((0, vec![start.to_string()], start.to_string())));
+
+        while let Some(Reverse((cost, _, node_name))) = heap.pop() {
+            if self.is_done_node(&node_name) {
+                continue;
+            }
+
+            if cost > self.node_costed(&node_name) {
+                continue;
+            }
+
+            let node_path_key = path_keys.get(&node_name).cloned().unwrap_or_else(|| vec![node_name.clone()]);
+
+            for edge in self.node_edges(&node_name) {
+                let passed_edge = edge.clone();
+                let next_node = edge.next;
+
+                if self.is_done_node(next_node.as_ref()) {
+                    continue;
+                }
+
+                let new_cost = cost + edge.cost;
+                let next_node_costed = self.node_costed(&next_node);
+
+                let mut candidate_key = node_path_key.clone();
+                candidate_key.push(next_node.clone());
+
+                let should_update = if !self.node_visited(&next_node) {
+                    true
+                } else if new_cost < next_node_costed {
+                    true
+                } else if new_cost == next_node_costed {
+                    path_keys.get(&next_node).is_none_or(|existing_key| candidate_key < *existing_key)
+                } else {
+                    false
+                };
+
+                if should_update {
+                    self.update_node_edge(&next_node, new_cost, &node_name, passed_edge);
+                    path_keys.insert(next_node.clone(), candidate_key.clone());
+                    heap.push(Reverse((new_cost, candidate_key, next_node)));
+                }
+            }
+
+            self.update_node_done(&node_name, true);
+
+            if node_name == goal {
+                break;
+            }
+        }
+
+        self.reconstruct_path(start, goal)
+    }
+
+    /// 辺名から`Edge`を引けるよう、グラフ全体の辺を一度だけ走査してマップに詰める。
+    fn edge_lookup(&self) -> HashMap<EdgeName, Edge> {
+        let mut map = HashMap::new();
+        for node in self.nodes.values() {
+            for edge in &node.edges {
+                map.insert(edge.name.clone(), edge.clone());
+            }
+        }
+        map
+    }
+
+    /// `start`を起点に、辺名の列`edge_names`を辿って`ShortestPath`を組み立て直す。
+    fn path_from_edges(&self, start: &str, edge_names: &[EdgeName], cost: i32, edge_lookup: &HashMap<EdgeName, Edge>) -> ShortestPath {
+        let mut passages = vec![WayKind::Node(start.to_string())];
+        for edge_name in edge_names {
+            if let Some(edge) = edge_lookup.get(edge_name) {
+                passages.push(WayKind::Edge(edge.name.clone(), edge.cost));
+                passages.push(WayKind::Node(edge.next.clone()));
+            }
+        }
+        ShortestPath {passages: passages, total_cost: cost}
+    }
+
+    /// グラフ全体に対してpruned landmark labeling（2-hopラベリング）を一度だけ行い、
+    /// 以降`DistanceIndex::distance`/`path`で高速に問い合わせられる索引を作る。
+    ///
+    /// ノードを次数の大きい順（ハブになりやすい順）に処理し、各根`r`について、通常の辺を
+    /// 辿るダイクストラで`r`から各ノードへの距離（`in`ラベル）を、逆辺を辿るダイクストラで
+    /// 各ノードから`r`への距離（`out`ラベル）を求める。ノードを確定・展開する前に、
+    /// 既存のラベル集合だけで同じ距離がもう再現できるか（共通のハブ経由で到達できるか）を
+    /// 調べ、再現できるなら枝刈りしてラベルを追加しない。これにより各ノードが持つラベル数を
+    /// 小さく保ちながら、`d(u, v) = min_h in_labels[v]∩out_labels[u] (out[u][h] + in[v][h])`
+    /// という定数時間に近いクエリを可能にする。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shortestpath::new_graph;
+    /// let mut g = new_graph();
+    ///
+    /// g.add("s", "a", 2, "edge1");
+    /// g.add("a", "z", 2, "edge2");
+    /// let index = g.build_hub_labels();
+    /// let distance = index.distance("s", "z");
+    /// ```
+    pub fn build_hub_labels(&self) -> DistanceIndex {
+        let mut order: Vec<GraphIndex> = self.nodes.keys().cloned().collect();
+        order.sort_by(|a, b| {
+            let degree_a = self.nodes.get(a).map(|node| node.edges.len()).unwrap_or(0);
+            let degree_b = self.nodes.get(b).map(|node| node.edges.len()).unwrap_or(0);
+            degree_b.cmp(&degree_a).then_with(|| a.cmp(b))
+        });
+
+        let forward_adjacency = self.forward_adjacency();
+        let backward_adjacency = self.reverse_adjacency();
+
+        let mut in_labels: HashMap<GraphIndex, Vec<(GraphIndex, i32)>> =
+            self.nodes.keys().map(|name| (name.clone(), vec![])).collect();
+        let mut out_labels: HashMap<GraphIndex, Vec<(GraphIndex, i32)>> =
+            self.nodes.keys().map(|name| (name.clone(), vec![])).collect();
+
+        for root in &order {
+            let new_in_labels = Self::label_from_root(&forward_adjacency, root, |name, _cost| {
+                Self::existing_distance(&out_labels, &in_labels, root, name)
+            });
+            for (node_name, dist) in new_in_labels {
+                let labels = in_labels.entry(node_name).or_default();
+                labels.push((root.clone(), dist));
+                labels.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+
+            let new_out_labels = Self::label_from_root(&backward_adjacency, root, |name, _cost| {
+                Self::existing_distance(&out_labels, &in_labels, name, root)
+            });
+            for (node_name, dist) in new_out_labels {
+                let labels = out_labels.entry(node_name).or_default();
+                labels.push((root.clone(), dist));
+                labels.sort_by(|a, b| a.0.cmp(&b.0));
+            }
+        }
+
+        DistanceIndex {graph: self.clone(), in_labels, out_labels}
+    }
+
+    /// 通常の辺向きの隣接リスト（ノード名 -> 出て行く辺）。
+    fn forward_adjacency(&self) -> HashMap<GraphIndex, Vec<Edge>> {
+        self.nodes.iter().map(|(name, node)| (name.clone(), node.edges.clone())).collect()
+    }
+
+    /// 辺の向きを逆にした隣接リスト。
+    fn reverse_adjacency(&self) -> HashMap<GraphIndex, Vec<Edge>> {
+        let mut reverse: HashMap<GraphIndex, Vec<Edge>> =
+            self.nodes.keys().map(|name| (name.clone(), vec![])).collect();
+        for (src, node) in &self.nodes {
+            for edge in &node.edges {
+                reverse.entry(edge.next.clone()).or_default().push(Edge {
+                    next: src.clone(),
+                    name: edge.name.clone(),
+                    cost: edge.cost,
+                });
+            }
+        }
+        reverse
+    }
+
+    /// `root`からの枝刈り付きダイクストラを行い、確定したノードとそのコストの一覧を返す。
+    fn label_from_root<F>(adjacency: &HashMap<GraphIndex, Vec<Edge>>, root: &str, mut existing: F) -> Vec<(GraphIndex, i32)>
+    where
+        F: FnMut(&str, i32) -> Option<i32>,
+    {
+        let mut best_cost: HashMap<GraphIndex, i32> = HashMap::new();
+        best_cost.insert(root.to_string(), 0);
+
+        let mut heap = BinaryHeap::new();
+        heap.push(Reverse((0, root.to_string())));
+
+        let mut labels = vec![];
+
+        while let Some(Reverse((cost, node_name))) = heap.pop() {
+            if let Some(&best) = best_cost.get(&node_name) {
+                if cost > best {
+                    continue;
+                }
+            }
+
+            if let Some(existing_cost) = existing(&node_name, cost) {
+                if existing_cost <= cost {
+                    continue;
                 }
             }
-            
-            let mut passages = vec![];
-            let mut node_name = goal.to_string();
-            loop {
-                if let Some(node) = self.nodes.get(&node_name) {
-                    passages.push(WayKind::Node(node.name.clone()));
-                    
-                    if let Some(ref passed_edge) = node.passage {
-                        passages.push(WayKind::Edge(passed_edge.name.clone(), passed_edge.cost));
+
+            labels.push((node_name.clone(), cost));
+
+            if let Some(edges) = adjacency.get(&node_name) {
+                for edge in edges {
+                    let next_cost = cost + edge.cost;
+                    let better = best_cost.get(&edge.next).is_none_or(|&best| next_cost < best);
+                    if better {
+                        best_cost.insert(edge.next.clone(), next_cost);
+                        heap.push(Reverse((next_cost, edge.next.clone())));
                     }
-                    
-                    if node.name == start {
-                        break;
+                }
+            }
+        }
+
+        labels
+    }
+
+    /// `out_labels[u]`と`in_labels[v]`をハブidでマージして`d(u, v)`の現在の推定値を返す。
+    fn existing_distance(
+        out_labels: &HashMap<GraphIndex, Vec<(GraphIndex, i32)>>,
+        in_labels: &HashMap<GraphIndex, Vec<(GraphIndex, i32)>>,
+        u: &str,
+        v: &str,
+    ) -> Option<i32> {
+        Self::best_hub(out_labels.get(u)?, in_labels.get(v)?).map(|(_, dist)| dist)
+    }
+
+    /// ソート済みの2つのラベル列を共通のハブidでマージし、合計距離が最小になるハブを返す。
+    fn best_hub(a: &[(GraphIndex, i32)], b: &[(GraphIndex, i32)]) -> Option<(GraphIndex, i32)> {
+        let mut i = 0;
+        let mut j = 0;
+        let mut best: Option<(GraphIndex, i32)> = None;
+
+        while i < a.len() && j < b.len() {
+            match a[i].0.cmp(&b[j].0) {
+                std::cmp::Ordering::Equal => {
+                    let total = a[i].1 + b[j].1;
+                    if best.as_ref().is_none_or(|(_, d)| total < *d) {
+                        best = Some((a[i].0.clone(), total));
                     }
-                    node_name = self.node_prev(&node_name).unwrap();
-                } else {
-                    break;
+                    i += 1;
+                    j += 1;
                 }
+                std::cmp::Ordering::Less => i += 1,
+                std::cmp::Ordering::Greater => j += 1,
             }
+        }
 
-            passages.reverse();
-            ShortestPath {passages: passages, total_cost: self.nodes.get(goal).unwrap().costed}
-        } else {
-            ShortestPath {passages: vec![], total_cost: -1}
+        best
+    }
+
+    /// 全ノードを始点としたダイクストラ探索を行い、各`(src, dst)`の組について、`src`から
+    /// 次にどの辺・ノードへ進めば最短経路になるか（next hop）とその総コストをまとめた
+    /// `RoutingTable`を返す。各ノードが行き先ごとの最短経路全体を覚える代わりに、次に
+    /// 転送すべき隣接ノードだけを知っていればよい、経路制御（ネットワークルーティング）の
+    /// ユースケースを想定している。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shortestpath::new_graph;
+    /// let mut g = new_graph();
+    ///
+    /// g.add("s", "a", 2, "edge1");
+    /// g.add("a", "z", 2, "edge2");
+    /// let table = g.routing_table();
+    /// let next_hop = table.next_hop("s", "z");
+    /// ```
+    pub fn routing_table(&mut self) -> RoutingTable {
+        let mut entries = HashMap::new();
+        let names: Vec<GraphIndex> = self.nodes.keys().cloned().collect();
+        self.reset_search_state();
+
+        for src in &names {
+            self.dijkstra_core(src, None, |_, cost| cost);
+
+            for dst in &names {
+                if dst == src || !self.node_visited(dst) {
+                    continue;
+                }
+
+                if let Some((edge_name, next_node)) = self.first_hop(src, dst) {
+                    let cost = self.node_costed(dst);
+                    entries.insert((src.clone(), dst.clone()), (edge_name, next_node, cost));
+                }
+            }
+
+            self.reset_search_state();
+        }
+
+        RoutingTable {entries: entries}
+    }
+
+    /// `prev`を`dst`から`src`方向へ辿り、`src`を出た直後の辺とノード（next hop）を求める。
+    fn first_hop(&self, src: &str, dst: &str) -> Option<(EdgeName, GraphIndex)> {
+        let mut current = dst.to_string();
+
+        loop {
+            let prev = self.node_prev(&current)?;
+            if prev == src {
+                let node = self.nodes.get(&current)?;
+                let passage = node.passage.as_ref()?;
+                return Some((passage.name.clone(), current));
+            }
+            current = prev;
+        }
+    }
+
+    /// 次の始点で再利用できるよう、探索状態（`done`/`costed`/`visited`/`passage`）を初期化する。
+    fn reset_search_state(&mut self) {
+        for node in self.nodes.values_mut() {
+            node.done = false;
+            node.costed = -1;
+            node.visited = false;
+            node.passage = None;
         }
     }
 }
 
+/// `Graph::routing_table`が返す、全始点・全終点の組について次の転送先をまとめた表。
+#[derive(Debug)]
+pub struct RoutingTable {
+    entries: HashMap<(GraphIndex, GraphIndex), (EdgeName, GraphIndex, i32)>,
+}
+
+impl RoutingTable {
+    /// `src`から`dst`へ向かう際に次に辿るべき辺名と隣接ノードを返す。
+    pub fn next_hop(&self, src: &str, dst: &str) -> Option<(EdgeName, GraphIndex)> {
+        self.entries.get(&(src.to_string(), dst.to_string())).map(|(edge_name, next_node, _)| (edge_name.clone(), next_node.clone()))
+    }
+
+    /// `src`から`dst`までの総コストを返す。
+    pub fn cost(&self, src: &str, dst: &str) -> Option<i32> {
+        self.entries.get(&(src.to_string(), dst.to_string())).map(|(_, _, cost)| *cost)
+    }
+}
+
+/// `Graph::build_hub_labels`が作る、2-hopラベリングに基づく距離索引。
+///
+/// `in_labels[v]`は「ハブ`h`から`v`への距離」、`out_labels[v]`は「`v`からハブ`h`への距離」を
+/// ハブid順に並べたもの。`distance`はこの2つをマージするだけの軽い問い合わせで済むが、
+/// 経路そのもの（`path`）はラベルに残していないため、最良のハブを求めたうえで`graph`の
+/// クローンに対して`shortest_path`を最大2回（ハブが`v`自身なら1回）走らせて繋ぎ合わせる。
+#[derive(Debug)]
+pub struct DistanceIndex {
+    graph: Graph,
+    in_labels: HashMap<GraphIndex, Vec<(GraphIndex, i32)>>,
+    out_labels: HashMap<GraphIndex, Vec<(GraphIndex, i32)>>,
+}
+
+impl DistanceIndex {
+    /// `u`から`v`への最短距離を、ハブラベルのマージだけで求める。
+    pub fn distance(&self, u: &str, v: &str) -> Option<i32> {
+        Graph::best_hub(self.out_labels.get(u)?, self.in_labels.get(v)?).map(|(_, dist)| dist)
+    }
+
+    /// `u`から`v`への最短経路を求める。最良のハブ`h`を介して`u -> h -> v`を繋ぎ合わせる。
+    pub fn path(&self, u: &str, v: &str) -> Option<ShortestPath> {
+        let (hub, _) = Graph::best_hub(self.out_labels.get(u)?, self.in_labels.get(v)?)?;
+
+        let mut graph = self.graph.clone();
+        let first_leg = graph.shortest_path(u, &hub);
+        if hub == v {
+            return Some(first_leg);
+        }
+
+        graph.reset_search_state();
+        let second_leg = graph.shortest_path(&hub, v);
+        Some(Self::concat_paths(first_leg, second_leg))
+    }
+
+    /// `first`の終点と`second`の始点が同じノード（ハブ）である前提で、重複するノードを
+    /// 1つにまとめて2つの経路を連結する。
+    fn concat_paths(first: ShortestPath, second: ShortestPath) -> ShortestPath {
+        let total_cost = first.total_cost + second.total_cost;
+        let mut passages = first.passages;
+        let mut second_passages = second.passages;
+        if !second_passages.is_empty() {
+            second_passages.remove(0);
+        }
+        passages.extend(second_passages);
+        ShortestPath {passages, total_cost}
+    }
+}
+
+/// 辺名の列をキーにした圧縮トライ。`k_shortest_paths`が見つけた経路（と検討中の候補）を、
+/// 共通する根経路の辺を重複して持たずに格納するために使う。
+#[derive(Debug, Default)]
+struct PathTrie {
+    nodes: Vec<(EdgeName, PathTrie)>,
+    leaf_cost: Option<i32>,
+}
+
+impl PathTrie {
+    fn new() -> Self {
+        PathTrie {nodes: vec![], leaf_cost: None}
+    }
+
+    fn insert(&mut self, edge_names: &[EdgeName], cost: i32) {
+        let mut current = self;
+        for edge_name in edge_names {
+            let idx = match current.nodes.iter().position(|(name, _)| name == edge_name) {
+                Some(idx) => idx,
+                None => {
+                    current.nodes.push((edge_name.clone(), PathTrie::new()));
+                    current.nodes.len() - 1
+                }
+            };
+            current = &mut current.nodes[idx].1;
+        }
+        current.leaf_cost = Some(cost);
+    }
+
+    fn contains(&self, edge_names: &[EdgeName]) -> bool {
+        let mut current = self;
+        for edge_name in edge_names {
+            match current.nodes.iter().find(|(name, _)| name == edge_name) {
+                Some((_, sub)) => current = sub,
+                None => return false,
+            }
+        }
+        current.leaf_cost.is_some()
+    }
+}
+
 #[derive(Debug)]
 pub struct ShortestPath {
     passages: Vec<WayKind>,
@@ -247,6 +925,28 @@ impl ShortestPath {
     pub fn cost(&self) -> i32 {
         self.total_cost
     }
+
+    /// 経路上のノード名を通過順に並べたもの。
+    fn node_names(&self) -> Vec<GraphIndex> {
+        self.passages.iter().filter_map(|way| {
+            if let WayKind::Node(name) = way {
+                Some(name.clone())
+            } else {
+                None
+            }
+        }).collect()
+    }
+
+    /// 経路上の辺名を通過順に並べたもの。
+    fn edge_names(&self) -> Vec<EdgeName> {
+        self.passages.iter().filter_map(|way| {
+            if let WayKind::Edge(name, _) = way {
+                Some(name.clone())
+            } else {
+                None
+            }
+        }).collect()
+    }
 }
 
 #[cfg(test)]
@@ -274,4 +974,165 @@ mod tests {
         // let result = result.get_node_path();
         // dbg!(result);
     }
+
+    #[test]
+    fn shortest_path_unreachable_goal_returns_sentinel() {
+        let mut g = new_graph();
+        g.add("s", "a", 1, "edge1");
+        g.add("x", "y", 1, "edge2");
+        let result = g.shortest_path("s", "y");
+
+        assert_eq!(-1, result.cost());
+    }
+
+    #[test]
+    fn astar_finds_shortest_path_with_consistent_heuristic() {
+        let mut g = new_graph();
+        g.add("s", "a", 1, "edge1");
+        g.add("s", "b", 4, "edge2");
+        g.add("a", "b", 1, "edge3");
+        g.add("a", "z", 5, "edge4");
+        g.add("b", "z", 1, "edge5");
+
+        let estimate = |node: &str| match node {
+            "s" => 2,
+            "a" => 1,
+            "b" => 0,
+            _ => 0,
+        };
+        let result = g.astar("s", "z", estimate);
+
+        assert_eq!(3, result.cost());
+        assert_eq!("s->a->b->z", result.get_node_path_string("->"));
+    }
+
+    #[test]
+    fn bellman_ford_handles_negative_weights() {
+        let mut g = new_graph();
+        g.add("s", "a", 4, "edge1");
+        g.add("s", "b", 1, "edge2");
+        g.add("b", "a", -2, "edge3");
+        g.add("a", "z", 1, "edge4");
+        let result = g.shortest_path_bellman_ford("s", "z").unwrap();
+
+        assert_eq!(0, result.cost());
+        assert_eq!("s->b->a->z", result.get_node_path_string("->"));
+    }
+
+    #[test]
+    fn bellman_ford_picks_among_equal_cost_paths() {
+        let mut g = new_graph();
+        g.add("s", "a", 2, "edge1");
+        g.add("s", "b", 2, "edge2");
+        g.add("a", "z", 2, "edge3");
+        g.add("b", "z", 2, "edge4");
+        let result = g.shortest_path_bellman_ford("s", "z").unwrap();
+
+        assert_eq!(4, result.cost());
+    }
+
+    #[test]
+    fn bellman_ford_detects_negative_cycle() {
+        let mut g = new_graph();
+        g.add("s", "a", 1, "edge1");
+        g.add("a", "b", 1, "edge2");
+        g.add("b", "a", -3, "edge3");
+        g.add("a", "z", 1, "edge4");
+
+        assert!(g.shortest_path_bellman_ford("s", "z").is_none());
+    }
+
+    #[test]
+    fn k_shortest_paths_finds_distinct_paths_in_cost_order() {
+        let mut g = new_graph();
+        g.add("s", "a", 2, "edge1");
+        g.add("s", "b", 5, "edge2");
+        g.add("a", "b", 2, "edge3");
+        g.add("a", "c", 5, "edge4");
+        g.add("b", "c", 4, "edge5");
+        g.add("b", "d", 2, "edge6");
+        g.add("c", "z", 7, "edge7");
+        g.add("d", "c", 5, "edge8");
+        g.add("d", "z", 2, "edge9");
+
+        let results = g.k_shortest_paths("s", "z", 3);
+
+        let costs: Vec<i32> = results.iter().map(|r| r.cost()).collect();
+        assert_eq!(vec![8, 9, 14], costs);
+        assert_eq!("s->a->b->d->z", results[0].get_node_path_string("->"));
+        assert_eq!("s->b->d->z", results[1].get_node_path_string("->"));
+        assert_eq!("s->a->c->z", results[2].get_node_path_string("->"));
+    }
+
+    #[test]
+    fn k_shortest_paths_tracks_distinctness_by_edge_not_node_sequence() {
+        let mut g = new_graph();
+        g.add("s", "a", 1, "edge1");
+        g.add("a", "b", 5, "edge2");
+        g.add("a", "b", 1, "edge3");
+        g.add("b", "c", 5, "edge4");
+        g.add("b", "c", 4, "edge5");
+        g.add("c", "z", 4, "edge6");
+
+        let results = g.k_shortest_paths("s", "z", 4);
+
+        let costs: Vec<i32> = results.iter().map(|r| r.cost()).collect();
+        assert_eq!(vec![10, 11, 14, 15], costs);
+    }
+
+    #[test]
+    fn lexicographic_breaks_ties_by_smallest_node_sequence() {
+        let mut g = new_graph();
+        g.add("s", "a", 2, "edge1");
+        g.add("s", "b", 2, "edge2");
+        g.add("a", "z", 2, "edge3");
+        g.add("b", "z", 2, "edge4");
+        let result = g.shortest_path_lexicographic("s", "z");
+
+        assert_eq!(4, result.cost());
+        assert_eq!("s->a->z", result.get_node_path_string("->"));
+    }
+
+    #[test]
+    fn hub_labels_distance_and_path_match_dijkstra() {
+        let mut g = new_graph();
+        g.add("s", "a", 2, "edge1");
+        g.add("s", "b", 5, "edge2");
+        g.add("a", "b", 2, "edge3");
+        g.add("a", "c", 5, "edge4");
+        g.add("b", "c", 4, "edge5");
+        g.add("b", "d", 2, "edge6");
+        g.add("c", "z", 7, "edge7");
+        g.add("d", "c", 5, "edge8");
+        g.add("d", "z", 2, "edge9");
+
+        let index = g.build_hub_labels();
+        let expected = g.shortest_path("s", "z");
+
+        assert_eq!(expected.cost(), index.distance("s", "z").unwrap());
+        let path = index.path("s", "z").unwrap();
+        assert_eq!(expected.cost(), path.cost());
+        assert_eq!("s->a->b->d->z", path.get_node_path_string("->"));
+    }
+
+    #[test]
+    fn routing_table_next_hop_and_cost_match_dijkstra() {
+        let mut g = new_graph();
+        g.add("s", "a", 2, "edge1");
+        g.add("s", "b", 5, "edge2");
+        g.add("a", "b", 2, "edge3");
+        g.add("a", "c", 5, "edge4");
+        g.add("b", "c", 4, "edge5");
+        g.add("b", "d", 2, "edge6");
+        g.add("c", "z", 7, "edge7");
+        g.add("d", "c", 5, "edge8");
+        g.add("d", "z", 2, "edge9");
+
+        let expected = g.shortest_path("s", "z");
+        let table = g.routing_table();
+
+        assert_eq!(Some(expected.cost()), table.cost("s", "z"));
+        assert_eq!(Some(("edge1".to_string(), "a".to_string())), table.next_hop("s", "z"));
+        assert_eq!(None, table.next_hop("s", "s"));
+    }
 }